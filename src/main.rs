@@ -11,6 +11,7 @@ use anyhow::{Context, Result};
 use argh::FromArgs;
 use std::env;
 use std::net::ToSocketAddrs;
+use std::path::PathBuf;
 
 /// Mere version from cargo manifest
 const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -29,6 +30,33 @@ struct Args {
     /// watch paths for changes using inotify
     #[argh(switch, short = 'w')]
     watch: bool,
+
+    /// trust and remember previously-unseen host keys, like
+    /// OpenSSH's StrictHostKeyChecking=accept-new
+    #[argh(switch)]
+    accept_new: bool,
+
+    /// number of parallel SSH sessions used for file transfers
+    #[argh(option, short = 'j', default = "1")]
+    jobs: usize,
+
+    /// preserve modification times on mirrored files
+    #[argh(switch, short = 't')]
+    times: bool,
+
+    /// private key file to try for authentication (repeatable, tried in
+    /// order; defaults to id_rsa, id_ed25519 and id_ecdsa in ~/.ssh)
+    #[argh(option, short = 'i')]
+    identity: Vec<String>,
+
+    /// environment variable holding a passphrase for the identity files
+    #[argh(option)]
+    passphrase_env: Option<String>,
+
+    /// environment variable holding a password, tried after key and agent
+    /// authentication fail
+    #[argh(option)]
+    password_env: Option<String>,
 }
 
 /// Main function
@@ -37,7 +65,19 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("mere v{VERSION}");
     let args: Args = argh::from_env();
     let dest = socket_addr(&args.destination)?;
-    Ok(mirror_files(args.watch, &dest, &args.path)?)
+    let identities =
+        args.identity.into_iter().map(PathBuf::from).collect();
+    Ok(mirror_files(
+        args.watch,
+        args.accept_new,
+        args.jobs,
+        args.times,
+        identities,
+        args.passphrase_env,
+        args.password_env,
+        &dest,
+        &args.path,
+    )?)
 }
 
 /// Validate destination host to parse as socket address
@@ -52,8 +92,26 @@ fn socket_addr(dest: &str) -> anyhow::Result<String> {
 }
 
 /// Mirror files to another host.
-fn mirror_files(watch: bool, dest: &str, paths: &[String]) -> Result<()> {
-    let mut mirror = Mirror::new(dest);
+fn mirror_files(
+    watch: bool,
+    accept_new: bool,
+    jobs: usize,
+    times: bool,
+    identities: Vec<PathBuf>,
+    passphrase_env: Option<String>,
+    password_env: Option<String>,
+    dest: &str,
+    paths: &[String],
+) -> Result<()> {
+    let mut mirror = Mirror::new(
+        dest,
+        accept_new,
+        jobs,
+        times,
+        identities,
+        passphrase_env,
+        password_env,
+    );
     for path in paths {
         mirror.add_path(path.into());
     }
@@ -3,20 +3,23 @@
 // Copyright (C) 2018-2025  Minnesota Department of Transportation
 //
 use anyhow::{Context, Result, anyhow};
-use inotify::{Event, Inotify, WatchDescriptor, WatchMask};
+use inotify::{Event, EventMask, Inotify, WatchDescriptor, WatchMask};
 use log::{debug, info, trace};
 use ssh2::{
-    ErrorCode, FileStat, OpenFlags, OpenType, RenameFlags, Session, Sftp,
+    CheckResult, ErrorCode, FileStat, KnownHostFileKind, OpenFlags, OpenType,
+    RenameFlags, Session, Sftp,
 };
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::env;
 use std::ffi::OsStr;
-use std::fs::{DirEntry, File, read_dir};
+use std::fs::{File, read_dir};
 use std::io;
 use std::net::TcpStream;
 use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, UNIX_EPOCH};
 
 /// Use 64 KB buffers
 const CAPACITY: usize = 64 * 1024;
@@ -29,6 +32,19 @@ pub struct Mirror {
     paths: HashSet<PathBuf>,
     /// User name
     username: String,
+    /// Trust previously-unseen host keys
+    accept_new: bool,
+    /// number of parallel sessions used for file transfers
+    jobs: usize,
+    /// preserve modification times on mirrored files
+    times: bool,
+    /// private key files to try, in order
+    identities: Vec<PathBuf>,
+    /// environment variable holding a passphrase for the identity files
+    passphrase_env: Option<String>,
+    /// environment variable holding a password, tried after key and agent
+    /// authentication fail
+    password_env: Option<String>,
 }
 
 /// Watcher for mirroring
@@ -42,24 +58,90 @@ pub struct Watcher {
 /// Get the inotify watch mask
 fn watch_mask() -> WatchMask {
     let mut mask = WatchMask::CLOSE_WRITE;
+    mask.insert(WatchMask::CREATE);
     mask.insert(WatchMask::DELETE);
     mask.insert(WatchMask::MOVE);
     mask
 }
 
+/// Check whether an inotify event is for a newly created or moved-in
+/// subdirectory
+fn is_new_directory_event(event: &Event<&OsStr>) -> bool {
+    event.mask.contains(EventMask::ISDIR)
+        && (event.mask.contains(EventMask::CREATE)
+            || event.mask.contains(EventMask::MOVED_TO))
+}
+
+/// Add a watch for a path, recursing into subdirectories
+fn add_watches(
+    inotify: &Inotify,
+    watches: &mut HashMap<WatchDescriptor, PathBuf>,
+    path: &Path,
+) -> Result<()> {
+    trace!("add_watches: {:?}", path);
+    let wd = inotify
+        .watches()
+        .add(path, watch_mask())
+        .with_context(|| format!("Could not add watch {path:?}"))?;
+    watches.insert(wd, path.to_path_buf());
+    if path.is_dir() {
+        for entry in
+            read_dir(path).with_context(|| format!("read_dir {path:?}"))?
+        {
+            if let Ok(entry) = entry {
+                let child = entry.path();
+                if is_path_valid(&child) && child.is_dir() {
+                    add_watches(inotify, watches, &child)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 impl Mirror {
     /// Create a new mirror.
     ///
     /// * `destination` Destination host and port.
-    pub fn new(destination: &str) -> Self {
+    /// * `accept_new` Trust and remember previously-unseen host keys.
+    /// * `jobs` Number of parallel sessions used for file transfers.
+    /// * `times` Preserve modification times on mirrored files.
+    /// * `identities` Private key files to try, in order. When empty,
+    ///   defaults to id_rsa, id_ed25519 and id_ecdsa in the user's ~/.ssh.
+    /// * `passphrase_env` Environment variable holding the key passphrase.
+    /// * `password_env` Environment variable holding a fallback password.
+    pub fn new(
+        destination: &str,
+        accept_new: bool,
+        jobs: usize,
+        times: bool,
+        identities: Vec<PathBuf>,
+        passphrase_env: Option<String>,
+        password_env: Option<String>,
+    ) -> Self {
         let destination = destination.to_string();
         let paths = HashSet::new();
         let username = whoami::username();
+        let jobs = jobs.max(1);
+        let identities = if identities.is_empty() {
+            DEFAULT_IDENTITIES
+                .iter()
+                .map(|name| home_ssh_path(&username, name))
+                .collect()
+        } else {
+            identities
+        };
         info!("Mirroring to {} as user {}", destination, username);
         Mirror {
             destination,
             paths,
             username,
+            accept_new,
+            jobs,
+            times,
+            identities,
+            passphrase_env,
+            password_env,
         }
     }
 
@@ -84,21 +166,52 @@ impl Mirror {
             return Ok(());
         }
         let session = create_session(&self.destination)?;
-        authenticate_session(&session, &self.username)?;
+        let (host, port) = split_destination(&self.destination)?;
+        verify_host_key(&session, &self.username, host, port, self.accept_new)?;
+        authenticate_session(
+            &session,
+            &self.username,
+            &self.identities,
+            self.passphrase_env.as_deref(),
+            self.password_env.as_deref(),
+        )?;
         let sftp = session.sftp().context("creating sftp")?;
+        // Structural work (mkdir/rmdir/stale file removal) happens on this
+        // single session; changed files are queued and copied afterward by
+        // a pool of worker sessions.
+        let mut queue = Vec::new();
         for path in self.paths.drain() {
             match std::fs::metadata(&path) {
                 Ok(metadata) => {
                     if metadata.is_dir() {
-                        mirror_directory(&sftp, &path)?;
+                        if sftp.stat(&path).is_err() {
+                            sftp.mkdir(&path, 0o755)
+                                .with_context(|| {
+                                    format!("sftp mkdir {path:?}")
+                                })?;
+                            info!("created directory {:?}", path);
+                        }
+                        mirror_directory(&sftp, &path, &mut queue)?;
                     } else if metadata.is_file() {
-                        mirror_file(&sftp, &path)?;
+                        queue.push(path);
                     }
                 }
                 Err(_) => rm_file(&sftp, &path).context("deleting file")?,
             }
         }
-        Ok(())
+        drop(sftp);
+        drop(session);
+        copy_queued(
+            &self.destination,
+            &self.username,
+            self.accept_new,
+            &self.identities,
+            self.passphrase_env.as_deref(),
+            self.password_env.as_deref(),
+            queue,
+            self.jobs,
+            self.times,
+        )
     }
 }
 
@@ -106,18 +219,19 @@ impl Watcher {
     /// Create a new watcher.
     pub fn new(mirror: &Mirror) -> Result<Self> {
         let inotify = Inotify::init()?;
-        let mask = watch_mask();
         let mut watches = HashMap::new();
         for path in &mirror.paths {
-            let wd = inotify
-                .watches()
-                .add(path, mask)
-                .with_context(|| format!("Could not add watch {path:?}"))?;
-            watches.insert(wd, path.clone());
+            add_watches(&inotify, &mut watches, path)?;
         }
         Ok(Watcher { inotify, watches })
     }
 
+    /// Add watches for a newly created or moved-in directory
+    fn watch_new_directory(&mut self, path: &Path) -> Result<()> {
+        trace!("watch_new_directory: {:?}", path);
+        add_watches(&self.inotify, &mut self.watches, path)
+    }
+
     /// Wait for watch events
     pub fn wait_events(&mut self, mirror: &mut Mirror) -> Result<()> {
         trace!("wait_events");
@@ -128,7 +242,13 @@ impl Watcher {
                 .read_events_blocking(&mut buffer)
                 .context("inotify.read_events_blocking")?;
             for event in events {
+                let new_dir = is_new_directory_event(&event);
                 if let Some(path) = self.event_path(event) {
+                    if new_dir {
+                        if let Err(e) = self.watch_new_directory(&path) {
+                            debug!("watch_new_directory {path:?}: {}", e);
+                        }
+                    }
                     mirror.add_path(path);
                 }
             }
@@ -156,7 +276,13 @@ impl Watcher {
             Err(err) => return Err(err).context("inotify.read_events"),
         };
         for event in events {
+            let new_dir = is_new_directory_event(&event);
             if let Some(path) = self.event_path(event) {
+                if new_dir {
+                    if let Err(e) = self.watch_new_directory(&path) {
+                        debug!("watch_new_directory {path:?}: {}", e);
+                    }
+                }
                 more |= mirror.add_path(path);
             }
         }
@@ -214,38 +340,143 @@ fn create_session(destination: &str) -> Result<Session> {
     Ok(session)
 }
 
-/// Authenticate an SSH session.
+/// Split a destination into host and port
+fn split_destination(destination: &str) -> Result<(&str, u16)> {
+    let (host, port) = destination
+        .rsplit_once(':')
+        .ok_or_else(|| anyhow!("invalid destination {destination:?}"))?;
+    let port = port
+        .parse()
+        .with_context(|| format!("invalid port in {destination:?}"))?;
+    Ok((host, port))
+}
+
+/// Path to a file under a user's ~/.ssh directory
+fn home_ssh_path(username: &str, file: &str) -> PathBuf {
+    let mut path = PathBuf::new();
+    path.push("/home");
+    path.push(username);
+    path.push(".ssh");
+    path.push(file);
+    path
+}
+
+/// Path to a user's known_hosts file
+fn known_hosts_path(username: &str) -> PathBuf {
+    home_ssh_path(username, "known_hosts")
+}
+
+/// Default identity files tried when none are given with `--identity`
+const DEFAULT_IDENTITIES: [&str; 3] = ["id_rsa", "id_ed25519", "id_ecdsa"];
+
+/// Verify the remote host key against the user's known_hosts file.
+///
+/// * `session` SSH session, after handshake.
+/// * `username` User whose known_hosts file is checked.
+/// * `host` Destination host name or address.
+/// * `port` Destination port.
+/// * `accept_new` Trust and remember previously-unseen host keys.
+fn verify_host_key(
+    session: &Session,
+    username: &str,
+    host: &str,
+    port: u16,
+    accept_new: bool,
+) -> Result<()> {
+    trace!("verify_host_key {}:{}", host, port);
+    let mut known_hosts =
+        session.known_hosts().context("loading known_hosts")?;
+    let path = known_hosts_path(username);
+    // Missing known_hosts file is fine -- it just means no hosts are known yet
+    let _ = known_hosts.read_file(&path, KnownHostFileKind::OpenSSH);
+    let (key, key_type) = session
+        .host_key()
+        .ok_or_else(|| anyhow!("no host key presented by {host}"))?;
+    match known_hosts.check_port(host, port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::NotFound if accept_new => {
+            known_hosts
+                .add(host, key, "added by mere --accept-new", key_type.into())
+                .with_context(|| format!("adding host key for {host}"))?;
+            known_hosts
+                .write_file(&path, KnownHostFileKind::OpenSSH)
+                .with_context(|| format!("writing {path:?}"))?;
+            info!("trusting new host key for {}", host);
+            Ok(())
+        }
+        CheckResult::NotFound => Err(anyhow!(
+            "host key for {host} not found in known_hosts \
+             (use --accept-new to trust it)"
+        )),
+        CheckResult::Mismatch => Err(anyhow!(
+            "host key for {host} does not match known_hosts -- \
+             possible man-in-the-middle attack"
+        )),
+        CheckResult::Failure => {
+            Err(anyhow!("failed to check host key for {host}"))
+        }
+    }
+}
+
+/// Authenticate an SSH session using a configurable, ordered chain of
+/// methods: each identity file in turn, then the agent, then -- if
+/// configured -- a password read from the environment.
 ///
 /// * `session` SSH session.
 /// * `username` User to authenticate.
-fn authenticate_session(session: &Session, username: &str) -> Result<()> {
+/// * `identities` Private key files to try, in order.
+/// * `passphrase_env` Environment variable holding the key passphrase.
+/// * `password_env` Environment variable holding a fallback password.
+fn authenticate_session(
+    session: &Session,
+    username: &str,
+    identities: &[PathBuf],
+    passphrase_env: Option<&str>,
+    password_env: Option<&str>,
+) -> Result<()> {
     trace!("authenticate_session {}", username);
-    // First, try using key with no pass-phrase.  If that doesn't work,
-    // try using agent auth -- maybe we're running interactively
-    authenticate_pubkey(session, username)
-        .or_else(|_| authenticate_agent(session, username))
-        .with_context(|| {
-            format!("authentication failed for user {username}")
-        })?;
-    Ok(())
+    let passphrase = passphrase_env.and_then(|var| env::var(var).ok());
+    for identity in identities {
+        match authenticate_pubkey(
+            session,
+            username,
+            identity,
+            passphrase.as_deref(),
+        ) {
+            Ok(()) => return Ok(()),
+            Err(e) => debug!("pubkey auth with {identity:?} failed: {e}"),
+        }
+    }
+    match authenticate_agent(session, username) {
+        Ok(()) => return Ok(()),
+        Err(e) => debug!("agent auth failed: {e}"),
+    }
+    if let Some(var) = password_env {
+        let password = env::var(var)
+            .with_context(|| format!("reading password from ${var}"))?;
+        return authenticate_password(session, username, &password);
+    }
+    Err(anyhow!("authentication failed for user {username}"))
 }
 
-/// Authenticate an SSH session using public key.
+/// Authenticate an SSH session using a public key file.
 ///
 /// * `session` SSH session.
 /// * `username` User to authenticate.
-fn authenticate_pubkey(session: &Session, username: &str) -> Result<()> {
-    let mut key_file = PathBuf::new();
-    key_file.push("/home");
-    key_file.push(username);
-    key_file.push(".ssh");
-    key_file.push("id_rsa");
-    session.userauth_pubkey_file(username, None, &key_file, None)?;
-    debug!("authenticated {} using pubkey", username);
+/// * `key_file` Private key file.
+/// * `passphrase` Passphrase for the key, if any.
+fn authenticate_pubkey(
+    session: &Session,
+    username: &str,
+    key_file: &Path,
+    passphrase: Option<&str>,
+) -> Result<()> {
+    session.userauth_pubkey_file(username, None, key_file, passphrase)?;
+    debug!("authenticated {} using pubkey {:?}", username, key_file);
     Ok(())
 }
 
-/// Authenticate an SSH session using agent.
+/// Authenticate an SSH session using the running ssh-agent.
 ///
 /// * `session` SSH session.
 /// * `username` User to authenticate.
@@ -255,25 +486,91 @@ fn authenticate_agent(session: &Session, username: &str) -> Result<()> {
     Ok(())
 }
 
-/// Mirror one directory to destination host
-fn mirror_directory(sftp: &Sftp, dir: &Path) -> Result<()> {
+/// Authenticate an SSH session using a password.
+///
+/// * `session` SSH session.
+/// * `username` User to authenticate.
+/// * `password` Password for the user.
+fn authenticate_password(
+    session: &Session,
+    username: &str,
+    password: &str,
+) -> Result<()> {
+    session.userauth_password(username, password)?;
+    debug!("authenticated {} using password", username);
+    Ok(())
+}
+
+/// Mirror one directory to destination host, queueing changed files for
+/// copying instead of copying them directly.
+fn mirror_directory(
+    sftp: &Sftp,
+    dir: &Path,
+    queue: &mut Vec<PathBuf>,
+) -> Result<()> {
     trace!("mirror_directory: {:?}", dir);
     let mut remote = sftp_read_dir(sftp, dir)?;
     for entry in read_dir(dir).with_context(|| format!("read_dir {dir:?}"))? {
-        if let Some((path, len)) = path_len(entry) {
-            let pos = remote.iter().position(|p| p.0 == path);
-            let rfile = pos.map(|i| remote.swap_remove(i));
-            if is_path_valid(&path) && should_mirror(rfile, len) {
-                mirror_file(sftp, &path)?;
-            }
+        let Ok(entry) = entry else { continue };
+        let path = entry.path();
+        if !is_path_valid(&path) {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else { continue };
+        let pos = remote.iter().position(|p| p.0 == path);
+        let rfile = pos.map(|i| remote.swap_remove(i));
+        if metadata.is_dir() {
+            mirror_subdirectory(sftp, &path, rfile, queue)?;
+        } else if metadata.is_file()
+            && should_mirror(rfile, metadata.len(), mtime_secs(&metadata))
+        {
+            queue.push(path);
         }
     }
-    // remove files which are not in the local directory
-    for (path, _) in remote {
+    // remove files and directories which are not in the local directory
+    for (path, stat) in remote {
         if is_path_valid(&path) {
+            if stat.is_dir() {
+                rm_remote_directory(sftp, &path)?;
+            } else {
+                rm_file(sftp, &path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Ensure a remote subdirectory exists, then mirror its contents
+fn mirror_subdirectory(
+    sftp: &Sftp,
+    dir: &Path,
+    rdir: Option<(PathBuf, FileStat)>,
+    queue: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let exists = matches!(rdir, Some((_, stat)) if stat.is_dir());
+    if !exists {
+        sftp.mkdir(dir, 0o755)
+            .with_context(|| format!("sftp mkdir {dir:?}"))?;
+        info!("created directory {:?}", dir);
+    }
+    mirror_directory(sftp, dir, queue)
+}
+
+/// Remove a remote directory (and its contents) no longer present locally
+fn rm_remote_directory(sftp: &Sftp, dir: &Path) -> Result<()> {
+    trace!("rm_remote_directory: {:?}", dir);
+    for (path, stat) in sftp
+        .readdir(dir)
+        .with_context(|| format!("sftp readdir {dir:?}"))?
+    {
+        if stat.is_dir() {
+            rm_remote_directory(sftp, &path)?;
+        } else {
             rm_file(sftp, &path)?;
         }
     }
+    sftp.rmdir(dir).with_context(|| format!("sftp rmdir {dir:?}"))?;
+    info!("removed directory {:?}", dir);
     Ok(())
 }
 
@@ -282,47 +579,132 @@ fn sftp_read_dir(sftp: &Sftp, dir: &Path) -> Result<Vec<(PathBuf, FileStat)>> {
     let mut remote = sftp
         .readdir(dir)
         .with_context(|| format!("sftp readdir {dir:?}"))?;
-    remote.retain(|path_stat| path_stat.1.is_file());
+    remote.retain(|path_stat| path_stat.1.is_file() || path_stat.1.is_dir());
     Ok(remote)
 }
 
-/// Get the path and length of a directory entry file
-fn path_len(entry: std::io::Result<DirEntry>) -> Option<(PathBuf, u64)> {
-    if let Ok(entry) = entry {
-        if let Ok(metadata) = entry.metadata() {
-            if metadata.is_file() {
-                return Some((entry.path(), metadata.len()));
-            }
-        }
-    }
-    None
-}
-
-/// Check if a file should be mirrored
-fn should_mirror(rfile: Option<(PathBuf, FileStat)>, len: u64) -> bool {
+/// Check if a file should be mirrored.
+///
+/// A file is mirrored when the remote copy doesn't exist, differs in
+/// length, or is older than the local file -- comparing length alone
+/// misses edits that happen to keep the byte count unchanged.
+fn should_mirror(
+    rfile: Option<(PathBuf, FileStat)>,
+    len: u64,
+    mtime: u64,
+) -> bool {
     rfile.is_none() || {
         let rstat = rfile.unwrap().1; // can't be none
         let rlen = rstat.size.unwrap_or(0);
-        len != rlen
+        let rmtime = rstat.mtime.unwrap_or(0);
+        len != rlen || mtime > rmtime
     }
 }
 
+/// Get a file's modification time as Unix epoch seconds
+fn mtime_secs(metadata: &std::fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
 /// Mirror a file.
 ///
 /// * `sftp` Sftp instance.
 /// * `path` Path to file.
-fn mirror_file(sftp: &Sftp, path: &Path) -> Result<()> {
+fn mirror_file(sftp: &Sftp, path: &Path, times: bool) -> Result<()> {
     let t = Instant::now();
-    copy_file(sftp, path)?;
+    copy_file(sftp, path, times)?;
     info!("copied {:?} in {:?}", path, t.elapsed());
     Ok(())
 }
 
-/// Create a backup file path
+/// Copy a queue of files to the destination, spreading the work across a
+/// pool of worker threads, each with its own authenticated session.
+///
+/// * `destination` Destination host and port.
+/// * `username` User to authenticate.
+/// * `accept_new` Trust and remember previously-unseen host keys.
+/// * `identities` Private key files to try, in order.
+/// * `passphrase_env` Environment variable holding the key passphrase.
+/// * `password_env` Environment variable holding a fallback password.
+/// * `paths` Files to copy.
+/// * `jobs` Number of parallel sessions to use.
+/// * `times` Preserve modification times on mirrored files.
+fn copy_queued(
+    destination: &str,
+    username: &str,
+    accept_new: bool,
+    identities: &[PathBuf],
+    passphrase_env: Option<&str>,
+    password_env: Option<&str>,
+    paths: Vec<PathBuf>,
+    jobs: usize,
+    times: bool,
+) -> Result<()> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+    let jobs = jobs.max(1).min(paths.len());
+    trace!("copy_queued: {} files, {} jobs", paths.len(), jobs);
+    let queue = Arc::new(Mutex::new(VecDeque::from(paths)));
+    let workers: Vec<_> = (0..jobs)
+        .map(|_| {
+            let queue = Arc::clone(&queue);
+            let destination = destination.to_string();
+            let username = username.to_string();
+            let identities = identities.to_vec();
+            let passphrase_env = passphrase_env.map(str::to_string);
+            let password_env = password_env.map(str::to_string);
+            thread::spawn(move || -> Result<()> {
+                let session = create_session(&destination)?;
+                let (host, port) = split_destination(&destination)?;
+                verify_host_key(&session, &username, host, port, accept_new)?;
+                authenticate_session(
+                    &session,
+                    &username,
+                    &identities,
+                    passphrase_env.as_deref(),
+                    password_env.as_deref(),
+                )?;
+                let sftp = session.sftp().context("creating sftp")?;
+                loop {
+                    let path = queue.lock().unwrap().pop_front();
+                    let Some(path) = path else { break };
+                    mirror_file(&sftp, &path, times)?;
+                }
+                Ok(())
+            })
+        })
+        .collect();
+    let mut first_err = None;
+    for worker in workers {
+        let result = worker
+            .join()
+            .unwrap_or_else(|_| Err(anyhow!("worker thread panicked")));
+        if let Err(e) = result {
+            if first_err.is_none() {
+                first_err = Some(e);
+            }
+        }
+    }
+    match first_err {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Create a backup file path, unique to the file being copied so that
+/// concurrent workers copying different files in the same directory never
+/// collide on the same backup name.
 fn backup_file(path: &Path) -> PathBuf {
     let mut backup = PathBuf::new();
     backup.push(path.parent().unwrap());
-    backup.push(".mere~");
+    let name = path.file_name().unwrap().to_string_lossy();
+    backup.push(format!(".{name}.mere~"));
     backup
 }
 
@@ -339,17 +721,71 @@ fn rename_flags() -> Option<RenameFlags> {
 ///
 /// * `sftp` Sftp instance.
 /// * `path` Path to file.
-fn copy_file(sftp: &Sftp, path: &Path) -> Result<()> {
+/// * `times` Preserve modification time on the remote file.
+fn copy_file(sftp: &Sftp, path: &Path, times: bool) -> Result<()> {
     trace!("copy_file {:?}", path);
     let backup = backup_file(path);
-    let src = File::open(path)?;
-    let metadata = src.metadata()?;
+    let metadata = std::fs::metadata(path)?;
     let len = metadata.len();
     // Mask off higher mode bits to avoid a "file corrupt" error
     let mode = (metadata.permissions().mode() & 0o7777) as i32;
+    copy_file_whole(sftp, path, &backup, len, mode)?;
+    rename_file(sftp, &backup, path)?;
+    if times {
+        set_remote_times(sftp, path, &metadata)?;
+    }
+    Ok(())
+}
+
+/// Stamp a remote file's mtime/atime to match the local source, so the
+/// mirror is a faithful copy and later runs don't spuriously re-copy it.
+///
+/// * `sftp` Sftp instance.
+/// * `path` Remote path, already renamed into place.
+/// * `metadata` Local source file metadata.
+fn set_remote_times(
+    sftp: &Sftp,
+    path: &Path,
+    metadata: &std::fs::Metadata,
+) -> Result<()> {
+    let mtime = mtime_secs(metadata);
+    let atime = metadata
+        .accessed()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(mtime);
+    let stat = FileStat {
+        size: None,
+        uid: None,
+        gid: None,
+        perm: None,
+        atime: Some(atime),
+        mtime: Some(mtime),
+    };
+    sftp.setstat(path, stat)
+        .with_context(|| format!("sftp setstat {path:?}"))?;
+    Ok(())
+}
+
+/// Copy an entire file to the backup path with sftp.
+///
+/// * `sftp` Sftp instance.
+/// * `path` Path to local file.
+/// * `backup` Remote backup path to write.
+/// * `len` Expected length of `path`.
+/// * `mode` Permission bits for the remote file.
+fn copy_file_whole(
+    sftp: &Sftp,
+    path: &Path,
+    backup: &Path,
+    len: u64,
+    mode: i32,
+) -> Result<()> {
+    let src = File::open(path)?;
     let dst = sftp
         .open_mode(
-            &backup,
+            backup,
             OpenFlags::WRITE | OpenFlags::TRUNCATE,
             mode,
             OpenType::File,
@@ -362,7 +798,7 @@ fn copy_file(sftp: &Sftp, path: &Path) -> Result<()> {
     // remote sftp file must be "closed" before renaming
     drop(dst);
     if copied == len {
-        rename_file(sftp, &backup, path)
+        Ok(())
     } else {
         Err(anyhow!("copy length wrong: {} != {}", copied, len))
     }